@@ -1,13 +1,14 @@
 use std::{
     borrow::Borrow,
     clone::Clone,
+    cmp::Reverse,
     collections::{
         hash_map::{
             self,
             Entry::{Occupied, Vacant},
             RandomState,
         },
-        HashMap, VecDeque,
+        BinaryHeap, HashMap, VecDeque,
     },
     fmt::Debug,
     hash::Hash,
@@ -15,6 +16,14 @@ use std::{
     ptr::NonNull,
 };
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: Eq + Hash + serde::Serialize, V: serde::Serialize",
+        deserialize = "K: Eq + Hash + serde::Deserialize<'de>, V: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Trie<K, V> {
     value: Option<V>,
     children: HashMap<K, Trie<K, V>>,
@@ -73,6 +82,37 @@ where
         self.value.is_none() && self.children.is_empty()
     }
 
+    /// Descends to the node at the end of `prefix` and returns an `Entry`
+    /// over its `value` slot for in-place insert-or-update, mirroring
+    /// `HashMap::entry`. Obtaining the entry never mutates the trie by
+    /// itself: nodes for the unmatched tail of `prefix` are only created
+    /// once `or_insert`/`or_insert_with` actually writes a value.
+    pub fn entry<Q, I: IntoIterator<Item = K>>(&mut self, prefix: I) -> Entry<'_, K, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        let mut iter = prefix.into_iter();
+        while let Some(c) = iter.next() {
+            let tmp = node;
+            if !tmp.children.contains_key(c.borrow()) {
+                let mut rest = vec![c];
+                rest.extend(iter);
+                return Entry::Vacant(VacantEntry { node: tmp, rest });
+            }
+            node = tmp.children.get_mut(c.borrow()).unwrap();
+        }
+        if node.value.is_some() {
+            Entry::Occupied(node)
+        } else {
+            Entry::Vacant(VacantEntry {
+                node,
+                rest: Vec::new(),
+            })
+        }
+    }
+
     pub fn get_ref<Q: ?Sized, I: IntoIterator<Item = K>>(&self, prefix: I) -> Option<&Trie<K, V>>
     where
         K: Borrow<Q>,
@@ -88,6 +128,83 @@ where
         Some(node)
     }
 
+    pub fn get_ref_borrow<'q, Q, I: IntoIterator<Item = &'q Q>>(
+        &self,
+        prefix: I,
+    ) -> Option<&Trie<K, V>>
+    where
+        Q: ?Sized + Hash + Eq + 'q,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        for c in prefix {
+            match node.children.get(c) {
+                Some(next) => node = next,
+                None => return None,
+            }
+        }
+        Some(node)
+    }
+
+    /// Walks `prefix` element by element, recording the deepest node along
+    /// the way whose `value` is set, and returns that value together with
+    /// the number of elements consumed to reach it. Unlike `get_ref`, which
+    /// only matches an exact node, this keeps the best match seen so far
+    /// and stops as soon as the walk runs out of children, making it
+    /// suitable for longest-prefix-match lookups (routing tables, greedy
+    /// tokenizers) where the input may extend past the longest stored key.
+    pub fn get_longest_prefix<Q, I: IntoIterator<Item = K>>(
+        &self,
+        prefix: I,
+    ) -> Option<(usize, &V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        let mut depth = 0;
+        let mut best = node.value.as_ref().map(|v| (depth, v));
+        for c in prefix {
+            match node.children.get(c.borrow()) {
+                Some(next) => {
+                    node = next;
+                    depth += 1;
+                    if let Some(ref v) = node.value {
+                        best = Some((depth, v));
+                    }
+                }
+                None => break,
+            }
+        }
+        best
+    }
+
+    pub fn get_longest_prefix_borrow<'q, Q, I: IntoIterator<Item = &'q Q>>(
+        &self,
+        prefix: I,
+    ) -> Option<(usize, &V)>
+    where
+        Q: ?Sized + Hash + Eq + 'q,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        let mut depth = 0;
+        let mut best = node.value.as_ref().map(|v| (depth, v));
+        for c in prefix {
+            match node.children.get(c) {
+                Some(next) => {
+                    node = next;
+                    depth += 1;
+                    if let Some(ref v) = node.value {
+                        best = Some((depth, v));
+                    }
+                }
+                None => break,
+            }
+        }
+        best
+    }
+
     pub fn get_mut<Q: ?Sized, I: IntoIterator<Item = K>>(
         &mut self,
         prefix: I,
@@ -107,6 +224,25 @@ where
         Some(node)
     }
 
+    pub fn get_mut_borrow<'q, Q, I: IntoIterator<Item = &'q Q>>(
+        &mut self,
+        prefix: I,
+    ) -> Option<&mut Trie<K, V>>
+    where
+        Q: ?Sized + Hash + Eq + 'q,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        for c in prefix {
+            let tmp = node;
+            match tmp.children.get_mut(c) {
+                Some(next) => node = next,
+                None => return None,
+            }
+        }
+        Some(node)
+    }
+
     pub fn remove<Q: ?Sized, I: IntoIterator<Item = K>>(&mut self, prefix: I) -> Option<V>
     where
         K: Borrow<Q>,
@@ -186,6 +322,32 @@ where
         values
     }
 
+    pub fn values_prefix_borrow<'q, I, Q>(&'_ self, prefix: I) -> Vec<&'_ V>
+    where
+        I: IntoIterator<Item = &'q Q>,
+        V: Debug,
+        Q: ?Sized + Hash + Eq + 'q,
+        K: Borrow<Q> + Debug,
+    {
+        let mut node = self;
+        let mut values = Vec::new();
+        for c in prefix {
+            if let Some(ref v) = node.value {
+                values.push(v);
+            }
+            match node.children.get(c) {
+                Some(next) => node = next,
+                None => {
+                    break;
+                }
+            }
+        }
+        if let Some(ref v) = node.value {
+            values.push(v);
+        }
+        values
+    }
+
     pub fn iter(&'_ self) -> Iter<'_, K, V> {
         Iter {
             prefix: Vec::new(),
@@ -194,6 +356,178 @@ where
             stack: Vec::new(),
         }
     }
+
+    /// Descends to the node at the end of `prefix` and returns an `Iter`
+    /// that walks only that subtree, with each yielded `IterItem` carrying
+    /// the full key (the descended `prefix` plus the suffix walked from
+    /// there) rather than just the suffix. If `prefix` isn't present the
+    /// returned iterator simply yields nothing.
+    pub fn iter_prefix<Q, I: IntoIterator<Item = K>>(&'_ self, prefix: I) -> Iter<'_, K, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let mut node = self;
+        let mut path = Vec::new();
+        for c in prefix {
+            match node.children.get_key_value(c.borrow()) {
+                Some((k, next)) => {
+                    path.push(k);
+                    node = next;
+                }
+                None => {
+                    return Iter {
+                        prefix: path,
+                        started: true,
+                        node,
+                        stack: Vec::new(),
+                    };
+                }
+            }
+        }
+        Iter {
+            prefix: path,
+            started: false,
+            node,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Collects the values stored anywhere under `prefix`, i.e. the
+    /// "find all entries starting with X" query autocomplete needs.
+    pub fn find_completions<Q, I: IntoIterator<Item = K>>(&'_ self, prefix: I) -> Vec<&'_ V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.iter_prefix(prefix).map(|item| item.value).collect()
+    }
+
+    /// Ranks every value under `prefix` by `score` and returns the `k`
+    /// highest-scoring `(key, value)` pairs in descending order. Walks the
+    /// subtree with `iter_prefix` while keeping only the best `k` entries
+    /// seen so far in a bounded min-heap, so memory stays `O(k)` instead of
+    /// `O(subtree size)`.
+    pub fn top_k_completions<Q, I: IntoIterator<Item = K>, F>(
+        &'_ self,
+        prefix: I,
+        k: usize,
+        score: F,
+    ) -> Vec<(Vec<&'_ K>, &'_ V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+        F: Fn(&V) -> i64,
+    {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut heap: BinaryHeap<Reverse<Scored<K, V>>> = BinaryHeap::new();
+        for item in self.iter_prefix(prefix) {
+            heap.push(Reverse(Scored {
+                score: score(item.value),
+                path: item.prefix,
+                value: item.value,
+            }));
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        let mut results = Vec::with_capacity(heap.len());
+        while let Some(Reverse(s)) = heap.pop() {
+            results.push((s.path, s.value));
+        }
+        results.reverse();
+        results
+    }
+}
+
+/// A `(score, key, value)` triple ordered solely by `score`, used to back
+/// the bounded min-heap in `Trie::top_k_completions`.
+struct Scored<'a, K, V> {
+    score: i64,
+    path: Vec<&'a K>,
+    value: &'a V,
+}
+
+impl<'a, K, V> PartialEq for Scored<'a, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.score == other.score
+    }
+}
+impl<'a, K, V> Eq for Scored<'a, K, V> {}
+impl<'a, K, V> PartialOrd for Scored<'a, K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<'a, K, V> Ord for Scored<'a, K, V> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.score.cmp(&other.score)
+    }
+}
+
+/// A view into a single node's `value` slot, obtained via `Trie::entry`,
+/// mirroring `std::collections::hash_map::Entry`. A `Vacant` entry holds
+/// the deepest existing node reached plus the unmatched tail of the
+/// lookup prefix; that tail is only turned into real nodes once an
+/// `or_insert`/`or_insert_with` call writes a value.
+pub enum Entry<'a, K, V> {
+    Occupied(&'a mut Trie<K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// The not-yet-materialized remainder of an `entry()` lookup: `node` is
+/// the deepest node that already exists, and `rest` are the elements
+/// still needed to reach the entry's final position.
+pub struct VacantEntry<'a, K, V> {
+    node: &'a mut Trie<K, V>,
+    rest: Vec<K>,
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Hash + Eq,
+{
+    /// Ensures a value is present, inserting `default` if the entry is
+    /// vacant, and returns a mutable reference to it.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensures a value is present, inserting the result of `default` if the
+    /// entry is vacant, and returns a mutable reference to it.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(node) => node.value.as_mut().unwrap(),
+            Entry::Vacant(VacantEntry { node, rest }) => {
+                let mut node = node;
+                for c in rest {
+                    let tmp = node;
+                    node = tmp.children.entry(c).or_default();
+                }
+                node.value = Some(default());
+                node.value.as_mut().unwrap()
+            }
+        }
+    }
+
+    /// Applies `f` to the value if the entry is occupied, leaving it
+    /// untouched otherwise, and returns the (possibly modified) entry so
+    /// it can be chained into `or_insert`/`or_insert_with`.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(node) => {
+                if let Some(ref mut v) = node.value {
+                    f(v);
+                }
+                Entry::Occupied(node)
+            }
+            Entry::Vacant(node) => Entry::Vacant(node),
+        }
+    }
 }
 
 impl<V> Trie<char, V> {
@@ -215,6 +549,10 @@ impl<V> Trie<char, V> {
         self.get_mut(prefix.as_ref().chars())
     }
 
+    pub fn get_longest_prefix_str<S: AsRef<str>>(&self, prefix: S) -> Option<(usize, &V)> {
+        self.get_longest_prefix(prefix.as_ref().chars())
+    }
+
     pub fn remove_str<Q: ?Sized, S: AsRef<str>>(&mut self, prefix: S) -> Option<V>
     where
         Q: Hash + Eq,
@@ -231,18 +569,16 @@ impl<V> Trie<u8, V> {
         self.insert(prefix.as_ref().into_iter().cloned(), value)
     }
 
-    pub fn get_ref_str<Q: ?Sized, S: AsRef<[u8]>>(&self, prefix: S) -> Option<&Self>
-    where
-        Q: Hash + Eq,
-    {
-        self.get_ref(prefix.as_ref().into_iter().cloned())
+    pub fn get_ref_str<S: AsRef<[u8]>>(&self, prefix: S) -> Option<&Self> {
+        self.get_ref_borrow(prefix.as_ref().iter())
     }
 
-    pub fn get_mut_str<Q: ?Sized, S: AsRef<[u8]>>(&mut self, prefix: S) -> Option<&mut Self>
-    where
-        Q: Hash + Eq,
-    {
-        self.get_mut(prefix.as_ref().into_iter().cloned())
+    pub fn get_mut_str<S: AsRef<[u8]>>(&mut self, prefix: S) -> Option<&mut Self> {
+        self.get_mut_borrow(prefix.as_ref().iter())
+    }
+
+    pub fn get_longest_prefix_bytes<S: AsRef<[u8]>>(&self, prefix: S) -> Option<(usize, &V)> {
+        self.get_longest_prefix_borrow(prefix.as_ref().iter())
     }
 
     pub fn remove_str<Q: ?Sized, S: AsRef<[u8]>>(&mut self, prefix: S) -> Option<V>
@@ -282,6 +618,9 @@ where
         if !self.started {
             self.started = true;
             self.stack.push(self.node.children.iter());
+            if let Some(ref value) = self.node.value {
+                return Some(IterItem::new(self.prefix.clone(), value));
+            }
         }
         loop {
             let node = match self.stack.last_mut() {
@@ -385,6 +724,161 @@ mod tests {
         assert_eq!(trie.get_mut("stuff".chars()).unwrap().value, Some("okay"));
     }
 
+    #[test]
+    fn test_longest_prefix() {
+        let mut trie = Trie::new();
+        trie.insert("foo".chars(), 1);
+        trie.insert("foobar".chars(), 2);
+        assert_eq!(
+            Some((6, &2)),
+            trie.get_longest_prefix("foobarbaz".chars())
+        );
+        assert_eq!(Some((3, &1)), trie.get_longest_prefix("foob".chars()));
+        assert_eq!(None, trie.get_longest_prefix("nope".chars()));
+
+        trie.insert_str("foobarbazzz", 3);
+        assert_eq!(
+            Some((11, &3)),
+            trie.get_longest_prefix_str("foobarbazzzqux")
+        );
+    }
+
+    #[test]
+    fn test_iter_prefix() {
+        let mut trie = Trie::new();
+        trie.insert_str("foo", 1);
+        trie.insert_str("foobar", 2);
+        trie.insert_str("foobaz", 3);
+        trie.insert_str("quux", 4);
+
+        let mut completions = trie.find_completions("foo".chars());
+        completions.sort();
+        assert_eq!(vec![&1, &2, &3], completions);
+
+        let mut full_keys: Vec<String> = trie
+            .iter_prefix("foo".chars())
+            .map(|item| item.prefix.into_iter().collect())
+            .collect();
+        full_keys.sort();
+        assert_eq!(vec!["foo", "foobar", "foobaz"], full_keys);
+
+        assert!(trie.find_completions("nope".chars()).is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip_json() {
+        let mut trie = Trie::new();
+        trie.insert_str("foo", 1);
+        trie.insert_str("foobar", 2);
+        trie.insert_str("quux", 3);
+
+        let encoded = serde_json::to_string(&trie).unwrap();
+        let decoded: Trie<char, i32> = serde_json::from_str(&encoded).unwrap();
+
+        let mut expected = trie.values_vec();
+        let mut actual = decoded.values_vec();
+        expected.sort();
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip_bincode() {
+        let mut trie = Trie::new();
+        trie.insert_bytes(b"stuff", 1);
+        trie.insert_bytes(b"staff", 2);
+
+        let encoded = bincode::serialize(&trie).unwrap();
+        let decoded: Trie<u8, i32> = bincode::deserialize(&encoded).unwrap();
+
+        let mut expected = trie.values_vec();
+        let mut actual = decoded.values_vec();
+        expected.sort();
+        actual.sort();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_top_k_completions() {
+        let mut trie = Trie::new();
+        trie.insert_str("cat", 10);
+        trie.insert_str("car", 30);
+        trie.insert_str("cart", 5);
+        trie.insert_str("card", 20);
+        trie.insert_str("dog", 100);
+
+        let top = trie.top_k_completions("ca".chars(), 2, |v| *v as i64);
+        let scores: Vec<i32> = top.into_iter().map(|(_, v)| *v).collect();
+        assert_eq!(vec![30, 20], scores);
+
+        assert!(trie
+            .top_k_completions("ca".chars(), 0, |v| *v as i64)
+            .is_empty());
+
+        let all = trie.top_k_completions("ca".chars(), 100, |v| *v as i64);
+        let all_scores: Vec<i32> = all.into_iter().map(|(_, v)| *v).collect();
+        assert_eq!(vec![30, 20, 10, 5], all_scores);
+
+        // "car" is itself a stored key, so it must be a candidate for its
+        // own prefix query, not just its descendants ("cart").
+        let car_completions = trie.top_k_completions("car".chars(), 2, |v| *v as i64);
+        let car_scores: Vec<i32> = car_completions.into_iter().map(|(_, v)| *v).collect();
+        assert_eq!(vec![30, 20], car_scores);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut trie = Trie::new();
+        for word in ["a", "b", "a", "c", "a", "b"].iter() {
+            trie.entry(word.chars()).and_modify(|c| *c += 1).or_insert(1);
+        }
+        assert_eq!(Some(&3), trie.get_ref("a".chars()).and_then(|n| n.value.as_ref()));
+        assert_eq!(Some(&2), trie.get_ref("b".chars()).and_then(|n| n.value.as_ref()));
+        assert_eq!(Some(&1), trie.get_ref("c".chars()).and_then(|n| n.value.as_ref()));
+    }
+
+    #[test]
+    fn test_entry_is_lazy() {
+        let mut trie = Trie::new();
+        trie.insert_str("car", 1);
+
+        // Obtaining the entry alone, with no `or_insert*` call, must not
+        // create nodes for the unmatched tail ("t").
+        match trie.entry("cart".chars()) {
+            Entry::Occupied(_) => panic!("\"cart\" was never inserted"),
+            Entry::Vacant(_) => {}
+        }
+        assert!(trie.get_ref("cart".chars()).is_none());
+
+        trie.entry("cart".chars()).or_insert(5);
+        assert_eq!(Some(&5), trie.get_ref("cart".chars()).and_then(|n| n.value.as_ref()));
+    }
+
+    #[test]
+    fn test_borrow_based_lookups() {
+        let mut trie = Trie::new();
+        trie.insert_bytes(b"stuff", 1);
+        trie.insert_bytes(b"staff", 2);
+
+        let key: Vec<u8> = b"stuff".to_vec();
+        assert_eq!(
+            Some(&1),
+            trie.get_ref_borrow(key.iter()).and_then(|n| n.value.as_ref())
+        );
+        assert_eq!(
+            Some((5, &1)),
+            trie.get_longest_prefix_borrow(key.iter())
+        );
+        assert_eq!(Some(&1), trie.get_ref_str(&key).and_then(|n| n.value.as_ref()));
+
+        if let Some(node) = trie.get_mut_borrow(key.iter()) {
+            node.value = Some(42);
+        }
+        assert_eq!(Some(&42), trie.get_mut_str(&key).and_then(|n| n.value.as_ref()));
+    }
+
     #[test]
     fn test_bytes_iter() {
         let mut trie = Trie::new();